@@ -1,12 +1,53 @@
-use bollard::container::{Config, CreateContainerOptions, ListContainersOptions, LogOutput, RemoveContainerOptions, StartContainerOptions};
+use bollard::container::{
+    Config, CreateContainerOptions, ListContainersOptions, LogOutput, RemoveContainerOptions,
+    StartContainerOptions,
+};
 use bollard::exec::{CreateExecOptions, StartExecOptions, StartExecResults};
 use bollard::image::{CommitContainerOptions, CreateImageOptions};
 use bollard::models::ContainerSummary;
 use bollard::Docker;
 use futures_util::StreamExt;
-use std::process::exit;
+use std::process::{exit, Command};
 use std::time::Duration;
 
+/// Queries the host GPUs via `nvidia-smi` and returns a `TORCH_CUDA_ARCH_LIST`
+/// value (e.g. `"8.6"`, or `"7.5;8.6"` for a mixed fleet) covering exactly
+/// the compute capabilities present. Returns `None` when no GPU is detected
+/// (e.g. `nvidia-smi` is missing, or the host has no NVIDIA GPU).
+pub(crate) fn detect_torch_cuda_arch_list(verbose: bool) -> Option<String> {
+    let output = Command::new("nvidia-smi")
+        .args(["--query-gpu=compute_cap", "--format=csv,noheader"])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let mut compute_caps: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect();
+    compute_caps.sort();
+    compute_caps.dedup();
+
+    if compute_caps.is_empty() {
+        return None;
+    }
+
+    if verbose {
+        let arches = compute_caps
+            .iter()
+            .map(|cap| format!("sm_{}", cap.replace('.', "")))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("[+] Detected GPU architectures: {}", arches);
+    }
+
+    Some(compute_caps.join(";"))
+}
+
 pub struct DockerClient {
     docker: Docker,
     verbose: bool,
@@ -30,7 +71,10 @@ impl DockerClient {
     }
 
     pub(crate) async fn run_container(&self, container_name: &str, image_name: &str) {
-        println!("[+] Running container {} with image {}", container_name, image_name);
+        println!(
+            "[+] Running container {} with image {}",
+            container_name, image_name
+        );
 
         let stream = self.docker.create_image(
             Some(CreateImageOptions {
@@ -90,7 +134,10 @@ impl DockerClient {
     }
 
     pub(crate) async fn commit_container(&self, container_name: &str, image_name: &str) {
-        println!("[+] Committing container {} to image {}", container_name, image_name);
+        println!(
+            "[+] Committing container {} to image {}",
+            container_name, image_name
+        );
         self.docker
             .commit_container(
                 CommitContainerOptions {
@@ -106,12 +153,15 @@ impl DockerClient {
 
     pub(crate) async fn remove_image(&self, image_name: &str) {
         println!("[+] Removing image {}", image_name);
-        self.docker.remove_image(image_name, None, None).await.unwrap();
+        self.docker
+            .remove_image(image_name, None, None)
+            .await
+            .unwrap();
     }
 
-    pub(crate) async fn execute_commands(&self, container_id: &str, commands: Vec<&str>) {
+    pub(crate) async fn execute_commands(&self, container_id: &str, commands: Vec<String>) {
         for command in commands {
-            self.execute_command(container_id, command).await;
+            self.execute_command(container_id, &command).await;
         }
     }
 
@@ -151,7 +201,11 @@ impl DockerClient {
             .await;
 
         let mut printed = false;
-        if let Ok(StartExecResults::Attached { mut output, input: _ }) = stream {
+        if let Ok(StartExecResults::Attached {
+            mut output,
+            input: _,
+        }) = stream
+        {
             while let Some(result) = output.next().await {
                 match result {
                     Ok(LogOutput::StdOut { message }) => {
@@ -1,13 +1,146 @@
 use std::env;
+use std::fs;
 
 use clap::{Arg, ArgAction, Command};
 
-use client::DockerClient;
-use versions::VersionResolver;
+use client::{detect_torch_cuda_arch_list, DockerClient};
+use mirrors::{Mirror, MirrorRegistry};
+use versions::{Version, VersionResolver};
 
 mod client;
+mod mirrors;
 mod versions;
 
+/// Used when no GPU can be probed (e.g. building on a machine without the
+/// target GPU attached) so the image still compiles CUDA extensions for a
+/// broad, reasonable range of architectures.
+const DEFAULT_TORCH_CUDA_ARCH_LIST: &str = "6.0;6.1;7.0;7.5;8.0;8.6;8.9;9.0";
+
+/// Builds the apt + deadsnakes + pip setup commands for `version`, pointed at
+/// the given Ubuntu archive `mirror`.
+fn build_pip_commands(version: &Version, mirror: &Mirror) -> Vec<String> {
+    vec![
+        "echo 'export DEBIAN_FRONTEND=noninteractive' >> /etc/bash.bashrc".to_string(),
+        format!("echo 'deb {} bionic main universe' >> /etc/apt/sources.list", mirror.base_url),
+        format!("echo 'deb {} bionic-security main universe' >> /etc/apt/sources.list", mirror.base_url),
+        format!("echo 'deb {} bionic-updates main universe' >> /etc/apt/sources.list", mirror.base_url),
+        format!(
+            "sed -i.bak -r 's@http://(jp\\.)?archive\\.ubuntu\\.com/ubuntu/?@{}/@g' /etc/apt/sources.list",
+            mirror.base_url
+        ),
+        "apt update -y".to_string(),
+        "apt upgrade -y".to_string(),
+        "apt install -y --allow-downgrades git python3-pip software-properties-common packagekit policykit-1 libpam-systemd systemd systemd-sysv libsystemd0=245.4-4ubuntu3.21 networkd-dispatcher".to_string(),
+        "add-apt-repository -y ppa:deadsnakes/ppa".to_string(),
+        "apt update -y".to_string(),
+        format!(
+            "apt install -y python{} python{}-dev python-{}-venv",
+            version.get_python_semantic_version(),
+            version.get_python_semantic_version(),
+            version.get_python_semantic_version(),
+        ),
+        "apt autoremove -y".to_string(),
+        "apt clean".to_string(),
+        format!(
+            "update-alternatives --install /usr/bin/python python /usr/bin/python{} 1",
+            version.get_python_semantic_version(),
+        ),
+        format!(
+            "pip install torch=={} -f https://download.pytorch.org/whl/{}",
+            version.torch, version.accelerator,
+        ),
+        format!(
+            "pip install torchvision torchaudio -f https://download.pytorch.org/whl/{}",
+            version.accelerator,
+        ),
+        "mkdir /app".to_string(),
+    ]
+}
+
+/// Builds miniforge + conda setup commands for `version`, pointed at the
+/// given Ubuntu archive `mirror`, installing torch from the pytorch conda
+/// channel with the matching `pytorch-cuda` build. Avoids the deadsnakes PPA
+/// entirely by provisioning Python through conda.
+fn build_conda_commands(version: &Version, mirror: &Mirror) -> Vec<String> {
+    let torch_install = if version.accelerator == "cpu" {
+        format!(
+            "/opt/conda/bin/conda install -y -n cudalis -c pytorch pytorch=={} torchvision torchaudio cpuonly",
+            version.torch,
+        )
+    } else {
+        format!(
+            "/opt/conda/bin/conda install -y -n cudalis -c pytorch -c nvidia pytorch=={} torchvision torchaudio pytorch-cuda={}",
+            version.torch,
+            version.get_accelerator_semantic_version(),
+        )
+    };
+
+    let miniforge_arch = match env::consts::ARCH {
+        "aarch64" => "aarch64",
+        _ => "x86_64",
+    };
+
+    vec![
+        "echo 'export DEBIAN_FRONTEND=noninteractive' >> /etc/bash.bashrc".to_string(),
+        format!("echo 'deb {} bionic main universe' >> /etc/apt/sources.list", mirror.base_url),
+        format!("echo 'deb {} bionic-security main universe' >> /etc/apt/sources.list", mirror.base_url),
+        format!("echo 'deb {} bionic-updates main universe' >> /etc/apt/sources.list", mirror.base_url),
+        format!(
+            "sed -i.bak -r 's@http://(jp\\.)?archive\\.ubuntu\\.com/ubuntu/?@{}/@g' /etc/apt/sources.list",
+            mirror.base_url
+        ),
+        "apt update -y".to_string(),
+        "apt upgrade -y".to_string(),
+        "apt install -y --allow-downgrades git wget ca-certificates".to_string(),
+        format!(
+            "wget -q https://github.com/conda-forge/miniforge/releases/latest/download/Miniforge3-Linux-{}.sh -O /tmp/miniforge.sh",
+            miniforge_arch,
+        ),
+        "bash /tmp/miniforge.sh -b -p /opt/conda".to_string(),
+        "rm /tmp/miniforge.sh".to_string(),
+        "echo 'export PATH=/opt/conda/bin:$PATH' >> /etc/bash.bashrc".to_string(),
+        format!("/opt/conda/bin/conda create -y -n cudalis python={}", version.get_python_semantic_version()),
+        torch_install,
+        "/opt/conda/bin/conda clean -y --all".to_string(),
+        "echo 'conda activate cudalis' >> /etc/bash.bashrc".to_string(),
+        "mkdir /app".to_string(),
+    ]
+}
+
+/// Renders the resolved base image and ordered setup commands as a reviewable
+/// Dockerfile, instead of committing them into an opaque local image layer.
+fn render_dockerfile(base_image: &str, commands: &[String]) -> String {
+    let mut dockerfile = format!("FROM {}\n", base_image);
+    for command in commands {
+        dockerfile.push_str(&format!("RUN {}\n", command));
+    }
+    dockerfile
+}
+
+/// Renders a `.devcontainer/devcontainer.json` that builds from the generated
+/// Dockerfile and pins the resolved python/torch/accelerator versions in the
+/// container name.
+fn render_devcontainer(version: &Version) -> String {
+    let name = format!(
+        "cudalis-py{}-torch{}-{}",
+        version.get_python_semantic_version(),
+        version.torch,
+        version.get_accelerator_semantic_version()
+    );
+
+    let devcontainer = serde_json::json!({
+        "name": name,
+        "build": {
+            "dockerfile": "../Dockerfile"
+        },
+        "hostRequirements": {
+            "gpu": "optional"
+        }
+    });
+
+    serde_json::to_string_pretty(&devcontainer).unwrap()
+}
+
 #[tokio::main]
 async fn main() {
     let command = Command::new("cudalis")
@@ -33,8 +166,39 @@ async fn main() {
                 .short('c')
                 .long("cuda")
                 .value_name("VERSION")
+                .conflicts_with("rocm")
                 .help("CUDA version to use. If not specified, it will be automatically select the latest supported version"),
         )
+        .arg(
+            Arg::new("rocm")
+                .long("rocm")
+                .value_name("VERSION")
+                .conflicts_with("cuda")
+                .help("ROCm version to use, for building AMD-GPU images instead of CUDA ones"),
+        )
+        .arg(
+            Arg::new("cuda-arch")
+                .long("cuda-arch")
+                .value_name("ARCH_LIST")
+                .help("Override TORCH_CUDA_ARCH_LIST instead of auto-detecting it from the host GPU (e.g. \"8.0;8.6\")"),
+        )
+        .arg(
+            Arg::new("package-manager")
+                .long("package-manager")
+                .value_name("MANAGER")
+                .value_parser(["pip", "conda"])
+                .default_value("pip")
+                .help("Package manager used to install Python and PyTorch inside the image"),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_name("MODE")
+                .value_parser(["image", "dockerfile", "devcontainer"])
+                .default_value("image")
+                .help("Commit a local image, or emit a reviewable Dockerfile / devcontainer.json instead"),
+        )
         .arg(
             Arg::new("region")
                 .short('r')
@@ -64,33 +228,103 @@ async fn main() {
         format!("cp{}{}", python_parts[0], python_parts[1])
     });
     let torch_version = matches.get_one::<String>("torch").map(|v| v.to_string());
-    let mut cuda_version = matches.get_one::<String>("cuda").map(|v| {
+    let cuda_version = matches.get_one::<String>("cuda").map(|v| {
         let cuda_parts = v.split('.').collect::<Vec<_>>();
         format!("cu{}{}", cuda_parts[0], cuda_parts[1])
     });
-    let _region = matches.get_one::<String>("region").map(|v| v.to_string());
+    let rocm_version = matches
+        .get_one::<String>("rocm")
+        .map(|v| format!("rocm{}", v));
+    let mut accelerator_version = cuda_version.or(rocm_version);
+    let region = matches.get_one::<String>("region").map(|v| v.to_string());
     let verbose = matches.get_flag("verbose");
     let _light = matches.get_flag("light");
 
-    if cuda_version.is_none() && env::consts::OS == "macos" {
-        cuda_version = Some("cpu".to_string());
+    if accelerator_version.is_none() && env::consts::OS == "macos" {
+        accelerator_version = Some("cpu".to_string());
     }
 
+    let output_mode = matches
+        .get_one::<String>("output")
+        .map(|v| v.as_str())
+        .unwrap_or("image");
+
+    let mirror = match region {
+        Some(region) => MirrorRegistry::find(&region).unwrap_or_else(|| {
+            eprintln!("[!] Unknown mirror region: {}", region);
+            std::process::exit(1);
+        }),
+        None => MirrorRegistry::auto_select(verbose).await,
+    };
+
     let resolver = VersionResolver::new(verbose);
-    let versions = resolver.resolve_versions(python_version, torch_version, cuda_version).await;
+    let versions = resolver
+        .resolve_versions(python_version, torch_version, accelerator_version)
+        .await;
     let version = versions.last().unwrap();
     let base_image = resolver.resolve_image_tag(version).await;
 
-    let client = DockerClient::new(verbose);
-
     println!(
-        "[+] Using resolved versions: python {}, torch {}, cuda {}, and tag {}",
+        "[+] Using resolved versions: python {}, torch {}, cuda {}, tag {}, and mirror {} ({})",
         version.get_python_semantic_version(),
         version.torch,
         version.get_accelerator_semantic_version(),
-        base_image
+        base_image,
+        mirror.name,
+        mirror.base_url
     );
 
+    let cuda_arch_export = if version.accelerator.starts_with("cu") {
+        let cuda_arch_list = matches
+            .get_one::<String>("cuda-arch")
+            .map(|v| v.to_string())
+            .or_else(|| detect_torch_cuda_arch_list(verbose))
+            .unwrap_or_else(|| DEFAULT_TORCH_CUDA_ARCH_LIST.to_string());
+
+        Some(format!(
+            "echo 'export TORCH_CUDA_ARCH_LIST=\"{}\"' >> /etc/bash.bashrc",
+            cuda_arch_list
+        ))
+    } else {
+        None
+    };
+
+    let package_manager = matches
+        .get_one::<String>("package-manager")
+        .map(|v| v.as_str())
+        .unwrap_or("pip");
+
+    if package_manager == "conda" && version.accelerator.starts_with("rocm") {
+        eprintln!("[!] --package-manager conda does not support --rocm (no pytorch-cuda build exists for ROCm); use --package-manager pip instead");
+        std::process::exit(1);
+    }
+
+    let mut commands = match package_manager {
+        "conda" => build_conda_commands(version, mirror),
+        _ => build_pip_commands(version, mirror),
+    };
+
+    if let Some(cuda_arch_export) = cuda_arch_export {
+        commands.push(cuda_arch_export);
+    }
+
+    if output_mode == "dockerfile" || output_mode == "devcontainer" {
+        let dockerfile = render_dockerfile(base_image.as_str(), &commands);
+        fs::write("Dockerfile", &dockerfile).unwrap();
+        println!("[+] Wrote Dockerfile");
+
+        if output_mode == "devcontainer" {
+            let devcontainer = render_devcontainer(version);
+            fs::create_dir_all(".devcontainer").unwrap();
+            fs::write(".devcontainer/devcontainer.json", devcontainer).unwrap();
+            println!("[+] Wrote .devcontainer/devcontainer.json");
+        }
+
+        return;
+    }
+
+    let client = DockerClient::new(verbose);
+
     let containers = client.list_containers().await;
 
     for container in containers {
@@ -102,44 +336,11 @@ async fn main() {
         }
     }
 
-    client.run_container("cudalis_setup", base_image.as_str()).await;
-    client.execute_commands(
-        "cudalis_setup",
-        vec![
-            "echo 'export DEBIAN_FRONTEND=noninteractive' >> /etc/bash.bashrc",
-            "echo 'deb https://ftp.udx.icscoe.jp/Linux/ubuntu bionic main universe' >> /etc/apt/sources.list",
-            "echo 'deb https://ftp.udx.icscoe.jp/Linux/ubuntu bionic-security main universe' >> /etc/apt/sources.list",
-            "echo 'deb https://ftp.udx.icscoe.jp/Linux/ubuntu bionic-updates main universe' >> /etc/apt/sources.list",
-            "sed -i.bak -r 's@http://(jp\\.)?archive\\.ubuntu\\.com/ubuntu/?@https://ftp.udx.icscoe.jp/Linux/ubuntu/@g' /etc/apt/sources.list",
-            "apt update -y",
-            "apt upgrade -y",
-            "apt install -y --allow-downgrades git python3-pip software-properties-common packagekit policykit-1 libpam-systemd systemd systemd-sysv libsystemd0=245.4-4ubuntu3.21 networkd-dispatcher",
-            "add-apt-repository -y ppa:deadsnakes/ppa",
-            "apt update -y",
-            &format!(
-                "apt install -y python{} python{}-dev python-{}-venv",
-                version.get_python_semantic_version(),
-                version.get_python_semantic_version(),
-                version.get_python_semantic_version(),
-            ),
-            "apt autoremove -y",
-            "apt clean",
-            &format!(
-                "update-alternatives --install /usr/bin/python python /usr/bin/python{} 1",
-                version.get_python_semantic_version(),
-            ),
-            &format!(
-                "pip install torch=={} -f https://download.pytorch.org/whl/{}",
-                version.torch,
-                version.accelerator,
-            ),
-            &format!(
-                "pip install torchvision torchaudio -f https://download.pytorch.org/whl/{}",
-                version.accelerator,
-            ),
-            "mkdir /app",
-        ],
-    ).await;
+    client
+        .run_container("cudalis_setup", base_image.as_str())
+        .await;
+
+    client.execute_commands("cudalis_setup", commands).await;
 
     let image_name = format!(
         "cudalis:{}-{}-{}",
@@ -152,5 +353,8 @@ async fn main() {
     client.remove_container("cudalis_setup").await;
     client.remove_image(base_image.as_str()).await;
 
-    println!("[+] Done. You can now use the image with tag: {}", image_name);
+    println!(
+        "[+] Done. You can now use the image with tag: {}",
+        image_name
+    );
 }
@@ -0,0 +1,103 @@
+use std::time::Instant;
+
+use futures_util::future::join_all;
+
+/// An Ubuntu archive mirror. `base_url` is the exact prefix used in
+/// `sources.list` entries (some mirrors, like the Japanese one below, nest
+/// the archive under an extra path segment).
+#[derive(Debug, Clone, Copy)]
+pub struct Mirror {
+    pub region: &'static str,
+    pub name: &'static str,
+    pub base_url: &'static str,
+}
+
+const MIRRORS: &[Mirror] = &[
+    Mirror {
+        region: "jp",
+        name: "Japan",
+        base_url: "https://ftp.udx.icscoe.jp/Linux/ubuntu",
+    },
+    Mirror {
+        region: "us",
+        name: "United States",
+        base_url: "http://mirrors.kernel.org/ubuntu",
+    },
+    Mirror {
+        region: "de",
+        name: "Germany",
+        base_url: "http://ftp.tu-chemnitz.de/pub/linux/ubuntu",
+    },
+    Mirror {
+        region: "uk",
+        name: "United Kingdom",
+        base_url: "http://mirror.bytemark.co.uk/ubuntu",
+    },
+    Mirror {
+        region: "au",
+        name: "Australia",
+        base_url: "http://mirror.aarnet.edu.au/pub/ubuntu/archive",
+    },
+    Mirror {
+        region: "sg",
+        name: "Singapore",
+        base_url: "http://mirror.nus.edu.sg/ubuntu",
+    },
+];
+
+pub struct MirrorRegistry;
+
+impl MirrorRegistry {
+    pub(crate) fn find(region: &str) -> Option<&'static Mirror> {
+        MIRRORS
+            .iter()
+            .find(|mirror| mirror.region.eq_ignore_ascii_case(region))
+    }
+
+    /// Probes every known mirror concurrently with an HTTP HEAD against its
+    /// `dists/` index and picks the one with the lowest round-trip time,
+    /// falling back to the first mirror if every probe fails.
+    pub(crate) async fn auto_select(verbose: bool) -> &'static Mirror {
+        if verbose {
+            println!("[+] Probing {} mirrors for latency", MIRRORS.len());
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(5))
+            .build()
+            .unwrap();
+
+        let probes = MIRRORS.iter().map(|mirror| {
+            let client = client.clone();
+            async move {
+                let url = format!("{}/dists/", mirror.base_url);
+                let start = Instant::now();
+
+                match client.head(&url).send().await {
+                    Ok(response)
+                        if response.status().is_success() || response.status().is_redirection() =>
+                    {
+                        Some((mirror, start.elapsed()))
+                    }
+                    _ => None,
+                }
+            }
+        });
+
+        let mut results: Vec<(&'static Mirror, std::time::Duration)> =
+            join_all(probes).await.into_iter().flatten().collect();
+        results.sort_by_key(|(_, latency)| *latency);
+
+        if verbose {
+            for (mirror, latency) in &results {
+                println!("    {} ({}): {:?}", mirror.name, mirror.region, latency);
+            }
+        }
+
+        results
+            .into_iter()
+            .next()
+            .map(|(mirror, _)| mirror)
+            .unwrap_or(&MIRRORS[0])
+    }
+}
@@ -1,19 +1,316 @@
+use std::cmp::Ordering;
 use std::env;
 use std::process::exit;
 
+/// A parsed PEP 440 release, used to compare and match versions numerically
+/// instead of lexicographically (e.g. `2.10.0` > `2.9.0`, `2.1` is a prefix
+/// of `2.1.0` but not of `2.11.0`). Local version segments (`+cuXXX`) are not
+/// handled since callers already strip them before parsing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pep440Version {
+    epoch: u32,
+    release: Vec<u32>,
+    pre: Option<(String, u32)>,
+    post: Option<u32>,
+    dev: Option<u32>,
+}
+
+impl Pep440Version {
+    fn parse(version: &str) -> Option<Self> {
+        let version = version.trim().to_lowercase();
+
+        let (epoch, rest) = match version.split_once('!') {
+            Some((epoch, rest)) => (epoch.parse().ok()?, rest),
+            None => (0, version.as_str()),
+        };
+
+        let release_end = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let (release_part, mut suffix) = rest.split_at(release_end);
+
+        let release: Vec<u32> = release_part
+            .split('.')
+            .map(|segment| segment.parse().ok())
+            .collect::<Option<Vec<_>>>()?;
+        if release.is_empty() {
+            return None;
+        }
+
+        let mut pre = None;
+        let mut post = None;
+        let mut dev = None;
+
+        loop {
+            suffix = suffix.trim_start_matches(['.', '-', '_']);
+            if suffix.is_empty() {
+                break;
+            }
+
+            if let Some(rest) = suffix
+                .strip_prefix("post")
+                .or_else(|| suffix.strip_prefix("rev"))
+                .or_else(|| suffix.strip_prefix('r'))
+            {
+                let (number, rest) = Self::take_number(rest);
+                post = Some(number);
+                suffix = rest;
+            } else if let Some(rest) = suffix.strip_prefix("dev") {
+                let (number, rest) = Self::take_number(rest);
+                dev = Some(number);
+                suffix = rest;
+            } else if let Some((label, rest)) =
+                ["alpha", "beta", "preview", "pre", "rc", "a", "b", "c"]
+                    .iter()
+                    .find_map(|label| suffix.strip_prefix(label).map(|rest| (*label, rest)))
+            {
+                let (number, rest) = Self::take_number(rest);
+                pre = Some((Self::normalize_pre_label(label), number));
+                suffix = rest;
+            } else {
+                break;
+            }
+        }
+
+        Some(Pep440Version {
+            epoch,
+            release,
+            pre,
+            post,
+            dev,
+        })
+    }
+
+    fn take_number(s: &str) -> (u32, &str) {
+        let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        (s[..end].parse().unwrap_or(0), &s[end..])
+    }
+
+    fn normalize_pre_label(label: &str) -> String {
+        match label {
+            "alpha" => "a",
+            "beta" => "b",
+            "c" | "pre" | "preview" => "rc",
+            other => other,
+        }
+        .to_string()
+    }
+
+    /// True if `self`'s release tuple has `prefix` as a leading subsequence,
+    /// e.g. release `[2, 1, 0]` has prefix `[2, 1]` but not `[2, 11]`.
+    fn release_starts_with(&self, prefix: &[u32]) -> bool {
+        self.release.starts_with(prefix)
+    }
+
+    /// Parses `s` as a PEP 440 version after skipping any non-numeric prefix,
+    /// e.g. the `cp` in `cp310` or the `cu` in `cu118`, so tag-style version
+    /// strings order numerically ("latest") instead of falling back to a
+    /// lexicographic string comparison where `cp39` would outrank `cp310`.
+    fn parse_loose(s: &str) -> Option<Self> {
+        let start = s.find(|c: char| c.is_ascii_digit())?;
+        Self::parse(&s[start..])
+    }
+}
+
+impl PartialOrd for Pep440Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pep440Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.epoch
+            .cmp(&other.epoch)
+            .then_with(|| Self::cmp_release(&self.release, &other.release))
+            .then_with(|| Self::cmp_pre(&self.pre, &other.pre))
+            .then_with(|| self.post.cmp(&other.post))
+            .then_with(|| Self::cmp_dev(&self.dev, &other.dev))
+    }
+}
+
+impl Pep440Version {
+    fn cmp_release(a: &[u32], b: &[u32]) -> Ordering {
+        for i in 0..a.len().max(b.len()) {
+            match a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0)) {
+                Ordering::Equal => continue,
+                ordering => return ordering,
+            }
+        }
+        Ordering::Equal
+    }
+
+    /// A final release outranks any of its pre-releases.
+    fn cmp_pre(a: &Option<(String, u32)>, b: &Option<(String, u32)>) -> Ordering {
+        match (a, b) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(a), Some(b)) => a.cmp(b),
+        }
+    }
+
+    /// A release outranks its own dev preview.
+    fn cmp_dev(a: &Option<u32>, b: &Option<u32>) -> Ordering {
+        match (a, b) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(a), Some(b)) => a.cmp(b),
+        }
+    }
+}
+
+/// The libc family and version a manylinux/musllinux wheel tag targets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WheelLibc {
+    Glibc { major: u32, minor: u32 },
+    Musl { major: u32, minor: u32 },
+    Unknown,
+}
+
+impl WheelLibc {
+    fn parse(platform_tag: &str) -> Self {
+        if let Some(glibc) = Self::parse_tagged("manylinux_", platform_tag) {
+            return glibc;
+        }
+        // Legacy tags predate the `manylinux_MAJOR_MINOR` scheme and map to a fixed glibc version.
+        if platform_tag.starts_with("manylinux1") {
+            return WheelLibc::Glibc { major: 2, minor: 5 };
+        }
+        if platform_tag.starts_with("manylinux2010") {
+            return WheelLibc::Glibc {
+                major: 2,
+                minor: 12,
+            };
+        }
+        if platform_tag.starts_with("manylinux2014") {
+            return WheelLibc::Glibc {
+                major: 2,
+                minor: 17,
+            };
+        }
+        if let Some(musl) = Self::parse_tagged("musllinux_", platform_tag) {
+            return match musl {
+                WheelLibc::Glibc { major, minor } => WheelLibc::Musl { major, minor },
+                other => other,
+            };
+        }
+
+        WheelLibc::Unknown
+    }
+
+    fn parse_tagged(prefix: &str, platform_tag: &str) -> Option<Self> {
+        let rest = platform_tag.strip_prefix(prefix)?;
+        let mut parts = rest.split('_');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next()?.parse().ok()?;
+        Some(WheelLibc::Glibc { major, minor })
+    }
+}
+
+/// The libc family and version of the host this tool is running on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HostLibc {
+    Glibc { major: u32, minor: u32 },
+    Musl { major: u32, minor: u32 },
+}
+
+impl HostLibc {
+    /// Detects the host's libc by inspecting the dynamic linker `/bin/sh` is
+    /// linked against: a `ld-musl-*` interpreter means musl, `ld-linux*`
+    /// means glibc. The matching version is then probed separately since the
+    /// interpreter path alone doesn't carry it.
+    fn detect() -> Option<Self> {
+        let interpreter = Self::read_interpreter("/bin/sh")?;
+
+        if interpreter.contains("ld-musl") {
+            let (major, minor) = Self::probe_musl_version(&interpreter)?;
+            Some(HostLibc::Musl { major, minor })
+        } else {
+            let (major, minor) = Self::probe_glibc_version()?;
+            Some(HostLibc::Glibc { major, minor })
+        }
+    }
+
+    fn read_interpreter(path: &str) -> Option<String> {
+        let data = std::fs::read(path).ok()?;
+        let text = String::from_utf8_lossy(&data);
+        text.split('\0')
+            .find(|s| s.contains("ld-musl") || s.contains("ld-linux"))
+            .map(|s| s.to_string())
+    }
+
+    fn probe_glibc_version() -> Option<(u32, u32)> {
+        let output = std::process::Command::new("ldd")
+            .arg("--version")
+            .output()
+            .ok()?;
+        let text = String::from_utf8_lossy(&output.stdout);
+        let version = text.lines().next()?.split_whitespace().last()?;
+        Self::parse_major_minor(version)
+    }
+
+    fn probe_musl_version(interpreter: &str) -> Option<(u32, u32)> {
+        // musl's dynamic linker prints its own version banner (to stderr) when run with no args.
+        let output = std::process::Command::new(interpreter).output().ok()?;
+        let text = String::from_utf8_lossy(&output.stderr);
+        let version_line = text
+            .lines()
+            .find(|line| line.trim_start().starts_with("Version"))?;
+        let version = version_line.split_whitespace().nth(1)?;
+        Self::parse_major_minor(version)
+    }
+
+    fn parse_major_minor(version: &str) -> Option<(u32, u32)> {
+        let mut parts = version.split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next()?.parse().ok()?;
+        Some((major, minor))
+    }
+
+    fn is_compatible_with(&self, wheel_libc: WheelLibc) -> bool {
+        match (self, wheel_libc) {
+            // PyTorch's own wheels are tagged plain `linux_x86_64` / `linux_aarch64` rather than
+            // manylinux/musllinux (they bundle their own libs and don't go through auditwheel), so
+            // an undeclared libc is assumed compatible; only an explicit, mismatching tag excludes.
+            (_, WheelLibc::Unknown) => true,
+            (
+                HostLibc::Glibc {
+                    major: host_major,
+                    minor: host_minor,
+                },
+                WheelLibc::Glibc { major, minor },
+            ) => major == *host_major && minor <= *host_minor,
+            (
+                HostLibc::Musl {
+                    major: host_major,
+                    minor: host_minor,
+                },
+                WheelLibc::Musl { major, minor },
+            ) => major == *host_major && minor <= *host_minor,
+            _ => false,
+        }
+    }
+}
+
 pub struct Version {
     pub name: String,
     pub torch: String,
     pub python: String,
     pub accelerator: String,
     pub os: String,
+    /// The unmodified platform tag (e.g. `manylinux_2_28_x86_64`,
+    /// `musllinux_1_1_x86_64`), kept alongside the normalized `os` field so
+    /// libc compatibility can still be probed after normalization.
+    pub platform_tag: String,
 }
 
 impl Version {
     fn parse_from_html_tag(tag: &str) -> Option<Self> {
         let parts: Vec<&str> = tag.split('"').collect();
         let href = parts.get(1)?;
-        if !href.starts_with("cpu") && !href.starts_with("cu") {
+        if !href.starts_with("cpu") && !href.starts_with("cu") && !href.starts_with("rocm") {
             return None;
         }
         let segments: Vec<&str> = href.split('/').collect();
@@ -22,21 +319,21 @@ impl Version {
         let name = package_parts.first()?.to_string();
         let torch = package_parts.get(1)?.split("%2B").next()?.to_string();
         let python = package_parts.get(2)?.to_string();
-        let os = package_parts
-            .get(4)?
-            .to_string()
+        let platform_tag = package_parts.get(4)?.to_string().replace(".whl", "");
+        let os = platform_tag
             .replace("win", "windows")
             .replace("macosx", "macos")
             .replace("manylinux", "linux")
+            .replace("musllinux", "linux")
             .replace("amd64", "x86_64")
-            .replace("arm64", "aarch64")
-            .replace(".whl", "");
+            .replace("arm64", "aarch64");
 
         Some(Version {
             name,
             torch,
             python,
             os,
+            platform_tag,
             accelerator,
         })
     }
@@ -51,6 +348,8 @@ impl Version {
     pub(crate) fn get_accelerator_semantic_version(&self) -> String {
         if self.accelerator == "cpu" {
             "cpu".to_string()
+        } else if let Some(rocm_version) = self.accelerator.strip_prefix("rocm") {
+            rocm_version.to_string()
         } else {
             let cuda_version = self.accelerator.replace("cu", "");
             let major = &cuda_version[0..2];
@@ -102,29 +401,51 @@ impl VersionResolver {
         versions = self.filter_versions_by_os_and_arch(versions);
 
         if self.verbose {
-            println!("    Found {} versions after filtering by OS and architecture", versions.len());
+            println!(
+                "    Found {} versions after filtering by OS and architecture",
+                versions.len()
+            );
         }
 
-        versions = self.filter_versions_by_specified_version(versions, python_version, |v| &v.python);
+        versions =
+            self.filter_versions_by_specified_version(versions, python_version, |v| &v.python);
 
         if self.verbose {
-            println!("    Found {} versions after filtering by Python version", versions.len());
+            println!(
+                "    Found {} versions after filtering by Python version",
+                versions.len()
+            );
         }
 
         versions = self.filter_versions_by_specified_version(versions, torch_version, |v| &v.torch);
 
         if self.verbose {
-            println!("    Found {} versions after filtering by Torch version", versions.len());
+            println!(
+                "    Found {} versions after filtering by Torch version",
+                versions.len()
+            );
         }
 
-        versions = self.filter_versions_by_specified_version(versions, cuda_version, |v| &v.accelerator);
+        versions =
+            self.filter_versions_by_specified_version(versions, cuda_version, |v| &v.accelerator);
 
         if self.verbose {
-            println!("    Found {} versions after filtering by CUDA version", versions.len());
+            println!(
+                "    Found {} versions after filtering by CUDA version",
+                versions.len()
+            );
             println!();
         }
 
-        versions.sort_by(|a, b| a.torch.cmp(&b.torch));
+        versions.sort_by(|a, b| {
+            match (
+                Pep440Version::parse_loose(&a.torch),
+                Pep440Version::parse_loose(&b.torch),
+            ) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                _ => a.torch.cmp(&b.torch),
+            }
+        });
 
         if versions.is_empty() {
             eprintln!("[!] No versions found with the specified constraints");
@@ -137,62 +458,120 @@ impl VersionResolver {
     pub(crate) async fn resolve_image_tag(&self, version: &Version) -> String {
         if version.accelerator == "cpu" {
             "ubuntu:22.04".to_string()
-        } else {
-            println!("[+] Resolving image tag with cuda {}", version.get_accelerator_semantic_version());
-            let result: serde_json::Value = reqwest::get(&format!(
-                "https://hub.docker.com/v2/repositories/nvidia/cuda/tags/?page_size=100&name={}",
-                version.get_accelerator_semantic_version()
-            ))
+        } else if version.accelerator.starts_with("rocm") {
+            self.resolve_repository_image_tag(
+                "rocm/dev-ubuntu-22.04",
+                "rocm",
+                version.get_accelerator_semantic_version().as_str(),
+            )
             .await
-            .unwrap()
-            .json()
+        } else {
+            self.resolve_repository_image_tag(
+                "nvidia/cuda",
+                "cuda",
+                version.get_accelerator_semantic_version().as_str(),
+            )
             .await
-            .unwrap();
+        }
+    }
 
-            if self.verbose {
-                println!("    Found {} tags", result["count"].as_u64().unwrap());
-            }
+    async fn resolve_repository_image_tag(
+        &self,
+        repository: &str,
+        accelerator_name: &str,
+        accelerator_version: &str,
+    ) -> String {
+        println!(
+            "[+] Resolving image tag with {} {}",
+            accelerator_name, accelerator_version
+        );
+        let result: serde_json::Value = reqwest::get(&format!(
+            "https://hub.docker.com/v2/repositories/{}/tags/?page_size=100&name={}",
+            repository, accelerator_version
+        ))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
 
-            let tags = result["results"]
-                .as_array()
-                .unwrap()
-                .iter()
-                .filter(|t| {
-                    let tag = t["name"].as_str().unwrap().to_string();
+        if self.verbose {
+            println!("    Found {} tags", result["count"].as_u64().unwrap());
+        }
 
-                    tag.starts_with(&version.get_accelerator_semantic_version()) && tag.contains("ubuntu") && tag.contains("devel")
-                })
-                .collect::<Vec<_>>();
+        let tags = result["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|t| {
+                let tag = t["name"].as_str().unwrap().to_string();
 
-            if self.verbose {
-                println!("    Found {} tags after filtering by CUDA version", tags.len());
-                println!();
-            }
+                tag.starts_with(accelerator_version)
+                    && (repository == "rocm/dev-ubuntu-22.04" || tag.contains("ubuntu"))
+                    && (repository == "rocm/dev-ubuntu-22.04" || tag.contains("devel"))
+            })
+            .collect::<Vec<_>>();
 
-            let tag = tags.iter().max_by(|a, b| {
-                let a_tag = a["name"].as_str().unwrap().to_string();
-                let b_tag = b["name"].as_str().unwrap().to_string();
+        if self.verbose {
+            println!(
+                "    Found {} tags after filtering by {} version",
+                tags.len(),
+                accelerator_name
+            );
+            println!();
+        }
 
-                a_tag.cmp(&b_tag)
-            });
+        let tag = tags.iter().max_by(|a, b| {
+            let a_tag = a["name"].as_str().unwrap().to_string();
+            let b_tag = b["name"].as_str().unwrap().to_string();
 
-            if tag.is_none() {
-                eprintln!("[!] No CUDA image found for version {}", version.get_accelerator_semantic_version());
-                exit(1);
-            }
+            a_tag.cmp(&b_tag)
+        });
 
-            format!("nvidia/cuda:{}", tag.unwrap()["name"].as_str().unwrap())
+        if tag.is_none() {
+            eprintln!(
+                "[!] No {} image found for version {}",
+                accelerator_name, accelerator_version
+            );
+            exit(1);
         }
+
+        format!("{}:{}", repository, tag.unwrap()["name"].as_str().unwrap())
     }
 
     fn filter_versions_by_os_and_arch(&self, versions: Vec<Version>) -> Vec<Version> {
         let computer_os = env::consts::OS.to_lowercase();
         let computer_arch = env::consts::ARCH.to_lowercase();
 
-        versions
+        let versions: Vec<Version> = versions
+            .into_iter()
+            .filter(|v| {
+                v.os.contains(&computer_os) && (v.os.contains("universal2") || v.os.contains(&computer_arch))
+            })
+            .collect();
+
+        if computer_os != "linux" {
+            return versions;
+        }
+
+        let Some(host_libc) = HostLibc::detect() else {
+            return versions;
+        };
+
+        let compatible: Vec<Version> = versions
             .into_iter()
-            .filter(|v| v.os.contains(&computer_os) && v.os.contains(&computer_arch))
-            .collect()
+            .filter(|v| host_libc.is_compatible_with(WheelLibc::parse(&v.platform_tag)))
+            .collect();
+
+        if compatible.is_empty() {
+            eprintln!(
+                "[!] No wheel compatible with the host libc ({:?}) was found",
+                host_libc
+            );
+            exit(1);
+        }
+
+        compatible
     }
 
     fn filter_versions_by_specified_version<F>(
@@ -205,21 +584,39 @@ impl VersionResolver {
         F: Fn(&Version) -> &String,
     {
         if let Some(specified_version) = specified_version {
-            versions
+            if let Some(specified) = Pep440Version::parse(&specified_version) {
+                return versions
+                    .into_iter()
+                    .filter(|v| {
+                        Pep440Version::parse(version_extractor(v))
+                            .map(|parsed| parsed.release_starts_with(&specified.release))
+                            .unwrap_or(false)
+                    })
+                    .collect();
+            }
+
+            return versions
                 .into_iter()
                 .filter(|v| version_extractor(v).contains(&specified_version))
-                .collect()
-        } else if let Some(latest_version) = versions
-            .iter()
-            .max_by(|a, b| version_extractor(a).cmp(version_extractor(b)))
-            .map(|v| version_extractor(v).clone())
-        {
-            versions
+                .collect();
+        }
+
+        let latest_version = versions.iter().max_by(|a, b| {
+            match (
+                Pep440Version::parse_loose(version_extractor(a)),
+                Pep440Version::parse_loose(version_extractor(b)),
+            ) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                _ => version_extractor(a).cmp(version_extractor(b)),
+            }
+        });
+
+        match latest_version.map(|v| version_extractor(v).clone()) {
+            Some(latest_version) => versions
                 .into_iter()
-                .filter(|v| version_extractor(v).contains(&latest_version))
-                .collect()
-        } else {
-            Vec::new()
+                .filter(|v| version_extractor(v) == &latest_version)
+                .collect(),
+            None => Vec::new(),
         }
     }
 }